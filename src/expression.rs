@@ -0,0 +1 @@
+_crit.bench_function("/*NAME*/", |b| b.iter(|| /*BLACK_BOX*/(/*EXPRESSION*/)));
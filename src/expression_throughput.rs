@@ -0,0 +1,8 @@
+{
+    let mut group = _crit.benchmark_group("/*NAME*/");
+    group.throughput(Throughput::/*THROUGHPUT_KIND*/(/*THROUGHPUT*/));
+    group.bench_with_input("/*NAME*/", &(/*THROUGHPUT*/), |b, _n| {
+        b.iter(|| /*BLACK_BOX*/(/*EXPRESSION*/));
+    });
+    group.finish();
+}
@@ -1,63 +1,87 @@
 use argh::FromArgs;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Deserialize;
 use std::{
+    collections::HashMap,
     env,
     fs::{self, File},
-    io::{Error, ErrorKind, Read as _, Write as _},
-    path::Path,
+    io::{Error, ErrorKind, Write as _},
+    path::{Path, PathBuf},
     process,
     str::FromStr,
+    sync::mpsc::{channel, RecvTimeoutError},
+    time::Duration,
 };
 
 const BASE: &str = "timeit";
 const BASE_DIR: &str = "rust-timeit";
 const CARGO_TOML: &str = include_str!("Cargo.toml.tmpl");
 const TIMEIT_EXPRESSION: &str = include_str!("expression.rs");
+const TIMEIT_THROUGHPUT_EXPRESSION: &str = include_str!("expression_throughput.rs");
 const TIMEIT_RS: &str = include_str!("timeit.rs");
 
 const CYCLES_DEP: &str = r#"criterion-cycles-per-byte = "0.1.2""#;
 const PERF_DEP: &str = r#"criterion-linux-perf = "0.1""#;
+const RESOURCE_DEP: &str = r#"procfs = "0.14""#;
 const CYCLES_USE: &str = "criterion_cycles_per_byte::CyclesPerByte";
 const PERF_USE: &str = "criterion_linux_perf::{PerfMeasurement, PerfMode}";
+const RESOURCE_RS: &str = include_str!("resource.rs");
 
-macro_rules! perf_mode {
-    ( $( $ident:ident => $word:literal, )* ) => {
+/// Declare a `FromStr`-able enum for a `--flag <value>` option that also
+/// accepts `help` to list its valid values and exit.
+macro_rules! mode_enum {
+    ( $flag:literal, $name:ident { $( $ident:ident => $word:literal, )* } ) => {
         #[derive(Clone, Copy, Debug, PartialEq)]
-        enum PerfMode {
+        enum $name {
             $( $ident, )*
         }
 
-        impl PerfMode {
-            fn as_perf_mode(&self) -> &'static str {
+        impl $name {
+            // Not every `mode_enum!` needs to round-trip a variant back
+            // into the generated bench source (e.g. OutputFormat, which
+            // is only ever matched directly), so this is dead code there.
+            #[allow(dead_code)]
+            fn as_variant_name(&self) -> &'static str {
                 match self {
                     $( Self::$ident => stringify!($ident), )*
                 }
             }
 
+            // Same rationale as `as_variant_name` above: not every
+            // `mode_enum!` round-trips a variant back into its own `--flag`
+            // word (e.g. OutputFormat), so this is dead code there.
+            #[allow(dead_code)]
+            fn as_word(&self) -> &'static str {
+                match self {
+                    $( Self::$ident => $word, )*
+                }
+            }
+
             fn all_modes() -> Vec<&'static str> {
                 vec![ $( $word, )* ]
             }
         }
 
-        impl FromStr for PerfMode {
+        impl FromStr for $name {
             type Err = String;
             fn from_str(s: &str) -> Result<Self, String> {
                 match s {
                     "help" => {
-                        eprintln!("Valid values for --perf");
+                        eprintln!("Valid values for {}", $flag);
                         for mode in Self::all_modes() {
                             eprintln!("  {}", mode);
                         }
                         process::exit(1);
                     }
                     $( $word => Ok(Self::$ident), )*
-                    _ => Err("Unknown perf mode".into()),
+                    _ => Err(format!("Unknown {} mode", $flag)),
                 }
             }
         }
     };
 }
 
-perf_mode! {
+mode_enum! { "--perf", PerfMode {
     Cycles => "cycles",
     Instructions => "instructions",
     Branches => "branches",
@@ -66,6 +90,31 @@ perf_mode! {
     CacheMisses => "cache-misses",
     BusCycles => "bus-cycles",
     RefCycles => "ref-cycles",
+} }
+
+mode_enum! { "--resource", ResourceKind {
+    MinorFaults => "minor-faults",
+    MajorFaults => "major-faults",
+    VoluntaryCtxsw => "voluntary-ctxsw",
+    InvoluntaryCtxsw => "involuntary-ctxsw",
+    MaxRss => "max-rss",
+} }
+
+mode_enum! { "--format", OutputFormat {
+    Human => "human",
+    Json => "json",
+} }
+
+mode_enum! { "--throughput-unit", ThroughputUnit {
+    Bytes => "bytes",
+    Elements => "elements",
+} }
+
+/// The criterion benchmark id for an expression, also used to locate its
+/// `target/criterion/<name>/...` results: the expression text itself, with
+/// the one character ('/') that would break a path component replaced.
+fn bench_name(expression: &str) -> String {
+    expression.replace('/', "_")
 }
 
 #[derive(Debug, FromArgs)]
@@ -87,6 +136,16 @@ struct Args {
     #[argh(option, short = 'i')]
     include: Vec<String>,
 
+    /// path to a TOML config file of named profiles (default:
+    /// `rust-timeit/config.toml` under `dirs::config_dir()`)
+    #[argh(option)]
+    config: Option<String>,
+
+    /// apply the named profile from the config file, prepending its
+    /// dependency/uses/include/setup defaults to whatever was passed here
+    #[argh(option)]
+    profile: Option<String>,
+
     /// use the CPU cycle count instead of wall time
     #[argh(switch)]
     cycles: bool,
@@ -97,14 +156,41 @@ struct Args {
     #[argh(option, short = 'p')]
     perf: Option<PerfMode>,
 
+    /// measure a /proc-derived resource counter instead of wall time (use
+    /// `--resource help` to list all the options for this)
+    #[cfg(target_os = "linux")]
+    #[argh(option, short = 'r')]
+    resource: Option<ResourceKind>,
+
     /// wrap the expressions in `criterion::black_box` to ensure their full evaluation
     #[argh(switch, short = 'b')]
     black_box: bool,
 
+    /// also report results as a rate over this many bytes/elements
+    /// processed per call; the time per iteration is still reported, and
+    /// `--format json` output stays per iteration
+    #[argh(option)]
+    throughput: Option<u64>,
+
+    /// select whether --throughput counts bytes or elements (default:
+    /// bytes; use `--throughput-unit help` to list all the options for
+    /// this)
+    #[argh(option, long = "throughput-unit")]
+    throughput_unit: Option<ThroughputUnit>,
+
     /// delete the cache directory before starting, making a fresh start
+    /// (this also wipes any saved baselines)
     #[argh(switch, short = 'f')]
     fresh: bool,
 
+    /// record this run's timings under the given criterion baseline name
+    #[argh(option, long = "save-baseline")]
+    save_baseline: Option<String>,
+
+    /// compare this run's timings against the given criterion baseline name
+    #[argh(option, long = "baseline")]
+    baseline: Option<String>,
+
     /// clean up the cache directory after a successful finish
     #[argh(switch, short = 'c')]
     cleanup: bool,
@@ -113,6 +199,17 @@ struct Args {
     #[argh(switch, short = 'v')]
     verbose: bool,
 
+    /// print each expression's final estimate as one JSON object per line
+    /// instead of criterion's human-readable report (use `--format help`
+    /// to list all the options for this)
+    #[argh(option)]
+    format: Option<OutputFormat>,
+
+    /// keep running, re-generating and re-running the benchmark whenever
+    /// one of the `--include`d files changes on disk
+    #[argh(switch, short = 'w')]
+    watch: bool,
+
     #[argh(positional)]
     expression: Vec<String>,
 }
@@ -126,6 +223,10 @@ impl Args {
         if self.perf.is_some() {
             self.dependency.push(PERF_DEP.into());
         }
+        #[cfg(target_os = "linux")]
+        if self.resource.is_some() {
+            self.dependency.push(RESOURCE_DEP.into());
+        }
         self.dependency.join("\n")
     }
 
@@ -144,19 +245,6 @@ impl Args {
             .join("")
     }
 
-    fn includes(&self) -> Result<String, Error> {
-        self.include
-            .iter()
-            .map(|filename| {
-                let mut contents = String::new();
-                fs::File::open(filename)
-                    .and_then(|mut file| file.read_to_string(&mut contents))
-                    .map(move |_| contents)
-            })
-            .collect::<Result<Vec<_>, _>>()
-            .map(|includes| includes.join("\n"))
-    }
-
     fn setup(&self) -> String {
         self.setup
             .as_ref()
@@ -165,11 +253,25 @@ impl Args {
     }
 
     fn expressions(&self) -> String {
+        let template = if self.throughput.is_some() {
+            TIMEIT_THROUGHPUT_EXPRESSION
+        } else {
+            TIMEIT_EXPRESSION
+        };
+        let throughput_kind = match self.throughput_unit {
+            Some(ThroughputUnit::Elements) => "Elements",
+            _ => "Bytes",
+        };
+        let throughput = self.throughput.unwrap_or_default().to_string();
+
         self.expression
             .iter()
             .map(|expression| {
                 let black_box = if self.black_box { "black_box" } else { "" };
-                TIMEIT_EXPRESSION
+                template
+                    .replace("/*NAME*/", &bench_name(expression))
+                    .replace("/*THROUGHPUT_KIND*/", throughput_kind)
+                    .replace("/*THROUGHPUT*/", &throughput)
                     .replace("/*BLACK_BOX*/", black_box)
                     .replace("/*EXPRESSION*/", expression)
             })
@@ -180,7 +282,11 @@ impl Args {
     fn timer(&self) -> String {
         #[cfg(target_os = "linux")]
         if let Some(mode) = self.perf {
-            return format!("PerfMeasurement::new(PerfMode::{})", mode.as_perf_mode());
+            return format!("PerfMeasurement::new(PerfMode::{})", mode.as_variant_name());
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(kind) = self.resource {
+            return format!("ResourceMeasurement::new(ResourceKind::{})", kind.as_variant_name());
         }
         if self.cycles {
             "CyclesPerByte".into()
@@ -188,6 +294,100 @@ impl Args {
             "WallTime".into()
         }
     }
+
+    fn resource(&self) -> String {
+        #[cfg(target_os = "linux")]
+        if self.resource.is_some() {
+            return RESOURCE_RS.into();
+        }
+        String::new()
+    }
+}
+
+/// A config value that may be written as either a single string or a list
+/// of strings, the same leniency cargo's own config format allows.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum StringOrVec {
+    String(String),
+    Vec(Vec<String>),
+}
+
+impl StringOrVec {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            Self::String(s) => vec![s],
+            Self::Vec(v) => v,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Profile {
+    dependency: Option<StringOrVec>,
+    uses: Option<StringOrVec>,
+    include: Option<StringOrVec>,
+    setup: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Config {
+    #[serde(default)]
+    profile: HashMap<String, Profile>,
+}
+
+fn config_path(args: &Args) -> Option<PathBuf> {
+    if let Some(path) = &args.config {
+        return Some(PathBuf::from(path));
+    }
+    let mut path = dirs::config_dir()?;
+    path.push(BASE_DIR);
+    path.push("config.toml");
+    Some(path)
+}
+
+/// Look up `--profile <name>` in the config file, if one was requested.
+fn load_profile(args: &Args) -> Result<Option<Profile>, Error> {
+    let Some(name) = &args.profile else {
+        return Ok(None);
+    };
+    let path = config_path(args)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "could not determine config directory"))?;
+    let contents = fs::read_to_string(&path)
+        .map_err(|error| Error::new(error.kind(), format!("{:?}: {}", path, error)))?;
+    let mut config: Config =
+        toml::from_str(&contents).map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
+    config.profile.remove(name).map(Some).ok_or_else(|| {
+        Error::new(
+            ErrorKind::NotFound,
+            format!("no profile named {:?} in {:?}", name, path),
+        )
+    })
+}
+
+/// Prepend a profile's defaults onto `args`, so CLI flags still add on top.
+fn apply_profile(args: &mut Args, profile: Profile) {
+    if let Some(dependency) = profile.dependency {
+        let mut merged = dependency.into_vec();
+        merged.append(&mut args.dependency);
+        args.dependency = merged;
+    }
+    if let Some(uses) = profile.uses {
+        let mut merged = uses.into_vec();
+        merged.append(&mut args.uses);
+        args.uses = merged;
+    }
+    if let Some(include) = profile.include {
+        let mut merged = include.into_vec();
+        merged.append(&mut args.include);
+        args.include = merged;
+    }
+    if let Some(setup) = profile.setup {
+        args.setup = Some(match args.setup.take() {
+            Some(cli_setup) => format!("{}; {}", setup, cli_setup),
+            None => setup,
+        });
+    }
 }
 
 fn create(filename: &str, template: &str, subst: &[(&str, &str)]) -> Result<(), Error> {
@@ -205,6 +405,103 @@ fn create(filename: &str, template: &str, subst: &[(&str, &str)]) -> Result<(),
     fs::rename(tempname, filename)
 }
 
+fn bench_cmdline(args: &Args) -> Vec<&str> {
+    let mut cmdline = vec!["bench", "--bench", "timeit", "--", "--noplot"];
+    if args.verbose {
+        cmdline.push("--verbose");
+    }
+    if let Some(name) = &args.save_baseline {
+        cmdline.push("--save-baseline");
+        cmdline.push(name);
+    }
+    if let Some(name) = &args.baseline {
+        cmdline.push("--baseline");
+        cmdline.push(name);
+    }
+    cmdline
+}
+
+/// Run `cargo bench`, swallowing criterion's human-readable report when
+/// `--format json` was requested so stdout stays pure JSON.
+fn run_bench(args: &Args) -> Result<process::ExitStatus, Error> {
+    let mut command = process::Command::new("cargo");
+    command.args(&bench_cmdline(args));
+    if matches!(args.format, Some(OutputFormat::Json)) {
+        command.stdout(process::Stdio::null());
+    }
+    command.status()
+}
+
+/// A stable, scriptable token identifying the currently selected
+/// measurement, for the JSON `measurement` field (unlike `timer()`, which
+/// returns a Rust constructor expression meant for the generated source).
+fn measurement_kind(args: &Args) -> &'static str {
+    #[cfg(target_os = "linux")]
+    if let Some(mode) = args.perf {
+        return mode.as_word();
+    }
+    #[cfg(target_os = "linux")]
+    if let Some(kind) = args.resource {
+        return kind.as_word();
+    }
+    if args.cycles {
+        "cycles"
+    } else {
+        "walltime"
+    }
+}
+
+/// The measurement unit criterion's raw `estimates.json` numbers are in
+/// for the currently selected measurement.
+fn unit(args: &Args) -> &'static str {
+    #[cfg(target_os = "linux")]
+    if args.perf.is_some() {
+        return "count";
+    }
+    #[cfg(target_os = "linux")]
+    if let Some(kind) = args.resource {
+        return if kind == ResourceKind::MaxRss {
+            "KB"
+        } else {
+            "count"
+        };
+    }
+    if args.cycles {
+        "cycles"
+    } else {
+        "ns"
+    }
+}
+
+/// Read one expression's `mean` estimate out of the `new/estimates.json`
+/// that criterion just wrote for it, and print it as a single JSON line.
+///
+/// In throughput mode each expression becomes its own `benchmark_group`
+/// whose single function shares its id, so criterion nests its results one
+/// level deeper: `target/criterion/<name>/<name>/new/estimates.json`.
+fn print_json_result(args: &Args, expression: &str) -> Result<(), Error> {
+    let name = bench_name(expression);
+    let path = if args.throughput.is_some() {
+        format!("target/criterion/{0}/{0}/new/estimates.json", name)
+    } else {
+        format!("target/criterion/{}/new/estimates.json", name)
+    };
+    let contents = fs::read_to_string(&path)?;
+    let estimates: serde_json::Value =
+        serde_json::from_str(&contents).map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
+    let mean = &estimates["mean"];
+    let result = serde_json::json!({
+        "expression": expression,
+        "measurement": measurement_kind(args),
+        "unit": unit(args),
+        "point_estimate": mean["point_estimate"],
+        "lower_bound": mean["confidence_interval"]["lower_bound"],
+        "upper_bound": mean["confidence_interval"]["upper_bound"],
+    });
+    println!("{}", result);
+    Ok(())
+}
+
 fn remove_dir_all<P: AsRef<Path>>(path: P) -> Result<(), Error> {
     fs::remove_dir_all(path).or_else(|error| match error.kind() {
         ErrorKind::NotFound => Ok(()),
@@ -212,21 +509,117 @@ fn remove_dir_all<P: AsRef<Path>>(path: P) -> Result<(), Error> {
     })
 }
 
+/// Canonicalize the `--include` filenames while still in the invocation
+/// directory, so they (and a filesystem watcher on them) keep working once
+/// `main` changes into the cache directory.
+fn canonical_include_paths(filenames: &[String]) -> Result<Vec<PathBuf>, Error> {
+    filenames.iter().map(fs::canonicalize).collect()
+}
+
+fn read_includes(paths: &[PathBuf]) -> Result<String, Error> {
+    paths
+        .iter()
+        .map(fs::read_to_string)
+        .collect::<Result<Vec<_>, _>>()
+        .map(|includes| includes.join("\n"))
+}
+
+/// Regenerate and re-run the benchmark whenever one of `include_paths`
+/// changes, coalescing a burst of saves within the debounce window into a
+/// single rerun.
+fn watch_and_rerun(
+    args: &Args,
+    include_paths: &[PathBuf],
+    uses: &str,
+    resource: &str,
+    setup: &str,
+    expressions: &str,
+    timer: &str,
+) -> Result<(), Error> {
+    const DEBOUNCE: Duration = Duration::from_millis(200);
+
+    let (tx, rx) = channel();
+    let mut watcher = RecommendedWatcher::new(tx, notify::Config::default())
+        .map_err(|error| Error::new(ErrorKind::Other, error))?;
+    for path in include_paths {
+        watcher
+            .watch(path, RecursiveMode::NonRecursive)
+            .map_err(|error| Error::new(ErrorKind::Other, error))?;
+    }
+
+    println!("Watching for changes; press Ctrl-C to stop.");
+    loop {
+        if rx.recv().is_err() {
+            return Ok(());
+        }
+        loop {
+            match rx.recv_timeout(DEBOUNCE) {
+                Ok(_) => continue,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+
+        println!("Change detected, rerunning benchmark.");
+        let includes = read_includes(include_paths)?;
+        create(
+            &format!("benches/{}.rs", BASE),
+            TIMEIT_RS,
+            &[
+                ("/*USES*/", uses),
+                ("/*INCLUDES*/", &includes),
+                ("/*RESOURCE*/", resource),
+                ("/*SETUP*/", setup),
+                ("/*EXPRESSIONS*/", expressions),
+                ("/*TIMER*/", timer),
+            ],
+        )?;
+
+        fs::remove_dir_all("target/criterion").ok();
+        run_bench(args)?;
+
+        if matches!(args.format, Some(OutputFormat::Json)) {
+            for expression in &args.expression {
+                print_json_result(args, expression)?;
+            }
+        }
+    }
+}
+
 fn main() -> Result<(), Error> {
     let mut args = argh::from_env::<Args>();
+    if let Some(profile) = load_profile(&args)? {
+        apply_profile(&mut args, profile);
+    }
     if args.expression.is_empty() {
         eprintln!("Please specify at least one expression");
         process::exit(1);
     }
 
+    if args.throughput_unit.is_some() && args.throughput.is_none() {
+        eprintln!("Cannot specify --throughput-unit without --throughput");
+        process::exit(1);
+    }
+
+    if args.watch && args.include.is_empty() {
+        eprintln!("Cannot specify --watch without at least one --include");
+        process::exit(1);
+    }
+
     #[cfg(target_os = "linux")]
-    if args.cycles && args.perf.is_some() {
-        eprintln!("Cannot specify both --cycles and --perf");
+    if [args.cycles, args.perf.is_some(), args.resource.is_some()]
+        .iter()
+        .filter(|&&selected| selected)
+        .count()
+        > 1
+    {
+        eprintln!("Cannot specify more than one of --cycles, --perf, --resource");
         process::exit(1);
     }
 
     // Pre-load the included files before changing the working directory
-    let includes = args.includes()?;
+    let include_paths = canonical_include_paths(&args.include)?;
+    let includes = read_includes(&include_paths)?;
 
     let mut base_dir = dirs::cache_dir().expect("Could not determine cache directory");
     base_dir.push(BASE_DIR);
@@ -247,25 +640,45 @@ fn main() -> Result<(), Error> {
         &[("@DEPENDENCIES@", &args.dependencies()), ("@BASE@", BASE)],
     )?;
 
+    let uses = args.uses();
+    let resource = args.resource();
+    let setup = args.setup();
+    let expressions = args.expressions();
+    let timer = args.timer();
+
     create(
         &format!("benches/{}.rs", BASE),
         TIMEIT_RS,
         &[
-            ("/*USES*/", &args.uses()),
+            ("/*USES*/", &uses),
             ("/*INCLUDES*/", &includes),
-            ("/*SETUP*/", &args.setup()),
-            ("/*EXPRESSIONS*/", &args.expressions()),
-            ("/*TIMER*/", &args.timer()),
+            ("/*RESOURCE*/", &resource),
+            ("/*SETUP*/", &setup),
+            ("/*EXPRESSIONS*/", &expressions),
+            ("/*TIMER*/", &timer),
         ],
     )?;
 
     fs::remove_dir_all("target/criterion").ok();
+    run_bench(&args)?;
 
-    let mut cmdline = vec!["bench", "--bench", "timeit", "--", "--noplot"];
-    if args.verbose {
-        cmdline.push("--verbose");
+    if matches!(args.format, Some(OutputFormat::Json)) {
+        for expression in &args.expression {
+            print_json_result(&args, expression)?;
+        }
+    }
+
+    if args.watch {
+        watch_and_rerun(
+            &args,
+            &include_paths,
+            &uses,
+            &resource,
+            &setup,
+            &expressions,
+            &timer,
+        )?;
     }
-    process::Command::new("cargo").args(&cmdline).status()?;
 
     if args.cleanup {
         println!("Deleting cache directory.");
@@ -0,0 +1,109 @@
+use criterion::measurement::ValueFormatter;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ResourceKind {
+    MinorFaults,
+    MajorFaults,
+    VoluntaryCtxsw,
+    InvoluntaryCtxsw,
+    MaxRss,
+}
+
+impl ResourceKind {
+    fn sample(&self) -> u64 {
+        let me = procfs::process::Process::myself().expect("could not read /proc/self");
+        match self {
+            Self::MinorFaults => me.stat().expect("could not read /proc/self/stat").minflt,
+            Self::MajorFaults => me.stat().expect("could not read /proc/self/stat").majflt,
+            Self::VoluntaryCtxsw => me
+                .status()
+                .expect("could not read /proc/self/status")
+                .voluntary_ctxt_switches
+                .unwrap_or(0),
+            Self::InvoluntaryCtxsw => me
+                .status()
+                .expect("could not read /proc/self/status")
+                .nonvoluntary_ctxt_switches
+                .unwrap_or(0),
+            Self::MaxRss => me
+                .status()
+                .expect("could not read /proc/self/status")
+                .vmhwm
+                .unwrap_or(0),
+        }
+    }
+
+    fn formatter(&self) -> &'static CountFormatter {
+        match self {
+            Self::MinorFaults | Self::MajorFaults => &CountFormatter { unit: "faults" },
+            Self::VoluntaryCtxsw | Self::InvoluntaryCtxsw => &CountFormatter { unit: "switches" },
+            Self::MaxRss => &CountFormatter { unit: "KB" },
+        }
+    }
+}
+
+struct CountFormatter {
+    unit: &'static str,
+}
+
+impl ValueFormatter for CountFormatter {
+    fn format_value(&self, value: f64) -> String {
+        format!("{:.0} {}", value, self.unit)
+    }
+
+    fn scale_values(&self, _typical_value: f64, _values: &mut [f64]) -> &'static str {
+        self.unit
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        self.unit
+    }
+
+    fn scale_throughputs(
+        &self,
+        _typical_value: f64,
+        _throughput: &Throughput,
+        _values: &mut [f64],
+    ) -> &'static str {
+        self.unit
+    }
+}
+
+struct ResourceMeasurement {
+    kind: ResourceKind,
+}
+
+impl ResourceMeasurement {
+    fn new(kind: ResourceKind) -> Self {
+        Self { kind }
+    }
+}
+
+impl Measurement for ResourceMeasurement {
+    type Intermediate = u64;
+    type Value = u64;
+
+    fn start(&self) -> Self::Intermediate {
+        self.kind.sample()
+    }
+
+    fn end(&self, start: Self::Intermediate) -> Self::Value {
+        self.kind.sample().saturating_sub(start)
+    }
+
+    fn add(&self, v1: &Self::Value, v2: &Self::Value) -> Self::Value {
+        v1 + v2
+    }
+
+    fn zero(&self) -> Self::Value {
+        0
+    }
+
+    fn to_f64(&self, value: &Self::Value) -> f64 {
+        *value as f64
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        self.kind.formatter()
+    }
+}
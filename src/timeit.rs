@@ -4,13 +4,15 @@
 use criterion::{
     black_box, criterion_group, criterion_main,
     measurement::{Measurement, WallTime},
-    Criterion,
+    Criterion, Throughput,
 };
 
 /*USES*/
 
 /*INCLUDES*/
 
+/*RESOURCE*/
+
 fn timeit<T: 'static + Measurement>(_crit: &mut Criterion<T>) {
     /*SETUP*/
 